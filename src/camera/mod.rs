@@ -0,0 +1,55 @@
+extern crate cgmath;
+
+use cgmath::{Matrix4, Point3, Vector3, EuclideanVector};
+use cgmath::{perspective, deg};
+
+/// A viewpoint onto the scene. The camera produces the view and projection
+/// matrices that `draw` uploads as uniforms, and can be orbited around its
+/// target or dollied towards it in response to input.
+pub struct Camera {
+    eye: Point3<f32>,
+    target: Point3<f32>,
+    up: Vector3<f32>,
+    fovy: f32,
+    aspect: f32,
+    near: f32,
+    far: f32,
+}
+
+impl Camera {
+    pub fn new() -> Camera {
+        Camera {
+            eye : Point3::new(0.0, 0.0, 3.0),
+            target : Point3::new(0.0, 0.0, 0.0),
+            up : Vector3::new(0.0, 1.0, 0.0),
+            fovy : 45.0,
+            aspect : 4.0 / 3.0,
+            near : 0.1,
+            far : 100.0,
+        }
+    }
+
+    pub fn view(&self) -> Matrix4<f32> {
+        Matrix4::look_at(&self.eye, &self.target, &self.up)
+    }
+
+    pub fn projection(&self) -> Matrix4<f32> {
+        perspective(deg(self.fovy), self.aspect, self.near, self.far)
+    }
+
+    /// Orbit the eye around the target about the up axis by `angle` radians.
+    pub fn orbit(&mut self, angle: f32) {
+        let offset = self.eye.sub_p(&self.target);
+        let cos = angle.cos();
+        let sin = angle.sin();
+        let x = offset.x * cos - offset.z * sin;
+        let z = offset.x * sin + offset.z * cos;
+        self.eye = self.target.add_v(&Vector3::new(x, offset.y, z));
+    }
+
+    /// Move the eye along the view direction; positive moves towards the target.
+    pub fn dolly(&mut self, amount: f32) {
+        let dir = self.target.sub_p(&self.eye).normalize();
+        self.eye = self.eye.add_v(&dir.mul_s(amount));
+    }
+}
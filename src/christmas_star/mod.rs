@@ -1,32 +1,56 @@
 extern crate gl;
 extern crate cgmath;
+extern crate image;
 
-use gl::types::{GLuint,GLfloat,GLsizeiptr,GLboolean};
-use std::ptr;
 use std::mem;
-use cgmath::{Vector3,Vector4,EuclideanVector};
+use std::collections::HashMap;
+use cgmath::{Vector2,Vector3,Vector4,Matrix4,EuclideanVector};
 
 use glutil;
 use game;
 use light;
 use control;
+use mesh;
+use camera;
+use device;
+use device::{Device,Buffer,VertexArray,Program,Texture,VertexAttribute};
 
 pub struct ChristmasStar {
     geometry: Geometry,
-    resource: GlResource,
+    resource: Option<GlResource>,
+    device: device::GlDevice,
     directional: light::Directional,
+    points: Vec<light::Point>,
+    material: light::Material,
+    ambient_color: cgmath::Vector3<f32>,
+    // Which light `update` currently steers: 0 is the directional light, 1..
+    // index into `points`. `update` cycles this when `control::State` reports
+    // its light-select input, so the arrow keys can move each light in turn.
+    active_light: uint,
+    camera: camera::Camera,
+    model: Matrix4<f32>,
 }
 
 pub struct Parameter<'a> {
     pub fragment_shader_path: &'a str,
     pub vertex_shader_path: &'a str,
+    // When present, the image at this path is loaded into a GL texture and
+    // sampled in the fragment shader; otherwise rendering stays untextured.
+    pub texture_path: Option<&'a str>,
 }
 
+// The GL resources backing a renderable object. Every GL object it holds is an
+// RAII handle from the `device`, so the whole object releases its GL state when dropped and
+// there is no manual `close()` to keep in sync. The vertex and element buffers
+// are held only to keep them alive for as long as the vertex array references
+// them.
 struct GlResource {
-    shader_program: GLuint,
-    vao: GLuint,
-    vbo: GLuint,
-    indice_num : i32,
+    program: Program,
+    vao: VertexArray,
+    vbo: Buffer,
+    ebo: Buffer,
+    texture: Option<Texture>,
+    indice_num: i32,
 }
 
 struct Geometry {
@@ -35,23 +59,48 @@ struct Geometry {
     right_canyon_offset : cgmath::Vector3<f32>,
     long_spike_length  : f32,
     short_spike_length : f32,
-    thickness : f32, 
+    thickness : f32,
 }
 
-struct Vertex {
+pub struct Vertex {
     position: cgmath::Vector3<f32>,
     normal: cgmath::Vector3<f32>,
     diffuse_color: cgmath::Vector4<f32>,
+    uv: cgmath::Vector2<f32>,
 }
 
 impl Vertex {
-    fn new(pos: cgmath::Vector3<f32>, norm: cgmath::Vector3<f32>, diffuse: cgmath::Vector4<f32>) -> Vertex {
+    pub fn new(pos: cgmath::Vector3<f32>,
+               norm: cgmath::Vector3<f32>,
+               diffuse: cgmath::Vector4<f32>,
+               uv: cgmath::Vector2<f32>) -> Vertex {
         Vertex {
             position : pos,
             normal : norm,
             diffuse_color : diffuse,
+            uv : uv,
         }
     }
+
+    // Describe how the fields of `Vertex` map onto vertex shader attributes.
+    // Offsets are read from the real field addresses of a sample vertex, so
+    // padding and field reordering are accounted for automatically and adding
+    // a field never silently shifts the others.
+    fn attributes() -> Vec<VertexAttribute> {
+        let v = Vertex::new(Vector3::new(0.0, 0.0, 0.0),
+                            Vector3::new(0.0, 0.0, 0.0),
+                            Vector4::new(0.0, 0.0, 0.0, 0.0),
+                            Vector2::new(0.0, 0.0));
+        let base = &v as *const Vertex as uint;
+        let offset_of = |field: uint| field - base;
+        let normalized = gl::FALSE as gl::types::GLboolean;
+        vec![
+            VertexAttribute { location: 0, component_count: 3, gl_type: gl::FLOAT, offset: offset_of(&v.position as *const _ as uint), normalized: normalized },
+            VertexAttribute { location: 1, component_count: 3, gl_type: gl::FLOAT, offset: offset_of(&v.normal as *const _ as uint), normalized: normalized },
+            VertexAttribute { location: 2, component_count: 4, gl_type: gl::FLOAT, offset: offset_of(&v.diffuse_color as *const _ as uint), normalized: normalized },
+            VertexAttribute { location: 3, component_count: 2, gl_type: gl::FLOAT, offset: offset_of(&v.uv as *const _ as uint), normalized: normalized },
+        ]
+    }
 }
 
 impl ChristmasStar {
@@ -65,102 +114,207 @@ impl ChristmasStar {
                 short_spike_length : 0.3,
                 thickness : 0.1,
             },
-            resource : GlResource {
-                shader_program : 0,
-                vao: 0,
-                vbo: 0,
-                indice_num: 0,
-            },
+            resource : None,
+            device : device::GlDevice::new(),
             directional : light::Directional::new("direction_to_light".to_string(), cgmath::Vector3::new(0.4, 0.5, 0.5)),
+            points : vec![
+                light::Point::new(cgmath::Vector3::new(0.6, 0.6, 0.6), 1.0, 0.09, 0.032),
+            ],
+            material : light::Material::new(
+                cgmath::Vector3::new(0.9, 0.9, 0.0),
+                cgmath::Vector3::new(0.9, 0.9, 0.0),
+                cgmath::Vector3::new(1.0, 1.0, 1.0),
+                32.0),
+            ambient_color : cgmath::Vector3::new(0.1, 0.1, 0.1),
+            active_light : 0,
+            camera : camera::Camera::new(),
+            model : Matrix4::identity(),
         }
     }
 
     pub fn init(&mut self, param: Parameter) -> Result<(), String> {
-        let vss = match glutil::read_shader(param.vertex_shader_path) {
-            Ok(s) => s,
-            Err(e) => return Err(format!("Failed reading vertex shader: {}", e)),
-        };
-        let fss = match glutil::read_shader(param.fragment_shader_path) {
-            Ok(s) => s,
-            Err(e) => return Err(format!("Failed reading fragment shader: {}", e)),
-        };
-        let vs = try!(glutil::compile_shader(vss.as_slice(), gl::VERTEX_SHADER));
-        let fs = try!(glutil::compile_shader(fss.as_slice(), gl::FRAGMENT_SHADER));
-        let prog = try!(glutil::link_program(vs, fs));
-
-        // remove shaders since we've finished linking it
-        glutil::remove_shader(prog, vs);
-        glutil::remove_shader(prog, fs);
- 
-        let (vao, vbo, ind_num) = try!(init_buffers(&self.geometry));
-
-        let r = &mut self.resource;
-        r.shader_program = prog;
-        r.vao = vao;
-        r.vbo = vbo;
-        r.indice_num = ind_num;
-
+        let vertices = generate_vertices(&self.geometry);
+        self.resource = Some(try!(build_resource(&self.device, &param, &vertices)));
         Ok(())
     }
 
-    pub fn close(&mut self) {
-        let r = &mut self.resource;
-        unsafe {
-            gl::DeleteBuffers(1, &r.vbo);
-            gl::DeleteVertexArrays(1, &r.vao);
+    // Nudge the currently selected light with the arrow keys. The directional
+    // light (index 0) moves in x/y; point lights move in x/y as well, which is
+    // enough to sweep them around the star while inspecting the shading.
+    fn move_active_light(&mut self, cs: &control::State) {
+        let delta = 0.02;
+        let mut offset = cgmath::Vector3::new(0.0, 0.0, 0.0);
+        if cs.move_up {
+            offset.y = offset.y + delta;
+        }
+        if cs.move_down {
+            offset.y = offset.y - delta;
+        }
+        if cs.move_left {
+            offset.x = offset.x - delta;
+        }
+        if cs.move_right {
+            offset.x = offset.x + delta;
+        }
+        if self.active_light == 0 {
+            self.directional.position = self.directional.position.add_v(&offset);
+        } else {
+            let light = &mut self.points[self.active_light - 1];
+            light.position = light.position.add_v(&offset);
         }
-        glutil::remove_program(r.shader_program);
-        r.shader_program = 0;
-        r.vbo = 0;
-        r.vao = 0;
     }
 }
 
 impl game::Object for ChristmasStar {
     fn update(&mut self, cs: &control::State) -> Result<(),String> {
-        let delta = 0.01;
+        // Cycle which light the arrow keys steer. The directional light sits at
+        // index 0, the point lights follow.
+        if cs.select_light {
+            self.active_light = (self.active_light + 1) % (self.points.len() + 1);
+        }
+        if cs.move_light {
+            self.move_active_light(cs);
+        } else {
+            let orbit_delta = 0.02;
+            let dolly_delta = 0.02;
+            if cs.move_up {
+                self.camera.dolly(dolly_delta);
+            }
+            if cs.move_down {
+                self.camera.dolly(-dolly_delta);
+            }
+            if cs.move_left {
+                self.camera.orbit(-orbit_delta);
+            }
+            if cs.move_right {
+                self.camera.orbit(orbit_delta);
+            }
+        }
+        Ok(())
+    }
+
+    fn draw(&self) -> Result<(),String> {
+        let r = match self.resource {
+            Some(ref r) => r,
+            None => return Err("ChristmasStar drawn before init".to_string()),
+        };
+        let d = &self.device;
+        try!(d.use_program(&r.program));
+
+        // upload the uniform variables on every draw
+        let dir_to_light = self.directional.position.sub(&self.geometry.center);
+        try!(d.set_uniform(&r.program, self.directional.name.as_slice(), &dir_to_light));
+
+        try!(set_lighting_uniforms(d, &r.program, &self.ambient_color,
+                                   &self.material, self.points.as_slice()));
+
+        try!(d.set_uniform(&r.program, "model", &self.model));
+        try!(d.set_uniform(&r.program, "view", &self.camera.view()));
+        try!(d.set_uniform(&r.program, "projection", &self.camera.projection()));
+
+        try!(bind_texture(d, &r.program, &r.texture));
+
+        try!(d.bind_vertex_array(&r.vao));
+        try!(d.draw_elements(r.indice_num));
+        try!(d.unbind_vertex_array());
+        Ok(())
+    }
+}
+
+/// A mesh loaded from an external source (e.g. a Wavefront OBJ file) rather
+/// than the procedural star. It reuses the same buffer setup and shading path
+/// as `ChristmasStar`; only the vertex source differs.
+pub struct Model {
+    vertices: Vec<Vertex>,
+    center: cgmath::Vector3<f32>,
+    resource: Option<GlResource>,
+    device: device::GlDevice,
+    directional: light::Directional,
+    points: Vec<light::Point>,
+    material: light::Material,
+    ambient_color: cgmath::Vector3<f32>,
+    camera: camera::Camera,
+    model: Matrix4<f32>,
+}
+
+impl Model {
+    /// Load a model from a Wavefront OBJ file, expanding its faces into the
+    /// existing `Vertex` layout.
+    pub fn from_obj(path: &str) -> Result<Model, String> {
+        let vertices = try!(mesh::load_obj(path));
+        Ok(Model {
+            vertices : vertices,
+            center : cgmath::Vector3::new(0.0,0.0,0.0),
+            resource : None,
+            device : device::GlDevice::new(),
+            directional : light::Directional::new("direction_to_light".to_string(), cgmath::Vector3::new(0.4, 0.5, 0.5)),
+            points : vec![
+                light::Point::new(cgmath::Vector3::new(0.6, 0.6, 0.6), 1.0, 0.09, 0.032),
+            ],
+            material : light::Material::new(
+                cgmath::Vector3::new(0.9, 0.9, 0.0),
+                cgmath::Vector3::new(0.9, 0.9, 0.0),
+                cgmath::Vector3::new(1.0, 1.0, 1.0),
+                32.0),
+            ambient_color : cgmath::Vector3::new(0.1, 0.1, 0.1),
+            camera : camera::Camera::new(),
+            model : Matrix4::identity(),
+        })
+    }
+
+    pub fn init(&mut self, param: Parameter) -> Result<(), String> {
+        self.resource = Some(try!(build_resource(&self.device, &param, &self.vertices)));
+        Ok(())
+    }
+}
+
+impl game::Object for Model {
+    fn update(&mut self, cs: &control::State) -> Result<(),String> {
+        let orbit_delta = 0.02;
+        let dolly_delta = 0.02;
         if cs.move_up {
-            self.directional.position.y += delta;
+            self.camera.dolly(dolly_delta);
         }
         if cs.move_down {
-            self.directional.position.y += -delta;
+            self.camera.dolly(-dolly_delta);
         }
         if cs.move_left {
-            self.directional.position.x += -delta;
+            self.camera.orbit(-orbit_delta);
         }
         if cs.move_right {
-            self.directional.position.x += delta;
+            self.camera.orbit(orbit_delta);
         }
-        // println!("directional: {}", self.directional.position);
         Ok(())
     }
 
     fn draw(&self) -> Result<(),String> {
-        let r = &self.resource;
-        unsafe {
-            gl::UseProgram(r.shader_program);
-            try!(glutil::check_error());
-
-            // update uniform variables if there were any change 
-            let cstr = self.directional.name.to_c_str();
-            let loc = gl::GetUniformLocation(r.shader_program, cstr.as_ptr());
-            try!(glutil::check_error());
-            let dir_to_light = self.directional.position.sub(&self.geometry.center);
-            gl::Uniform3f(loc, dir_to_light.x, dir_to_light.y, dir_to_light.z);
-            try!(glutil::check_error());
-
-            gl::BindVertexArray(r.vao);
-            try!(glutil::check_error());
-            gl::DrawArrays(gl::TRIANGLES, 0, r.indice_num);
-            try!(glutil::check_error());
-            gl::BindVertexArray(0);
-            gl::UseProgram(0);
-        }
+        let r = match self.resource {
+            Some(ref r) => r,
+            None => return Err("Model drawn before init".to_string()),
+        };
+        let d = &self.device;
+        try!(d.use_program(&r.program));
+
+        let dir_to_light = self.directional.position.sub(&self.center);
+        try!(d.set_uniform(&r.program, self.directional.name.as_slice(), &dir_to_light));
+
+        try!(set_lighting_uniforms(d, &r.program, &self.ambient_color,
+                                   &self.material, self.points.as_slice()));
+
+        try!(d.set_uniform(&r.program, "model", &self.model));
+        try!(d.set_uniform(&r.program, "view", &self.camera.view()));
+        try!(d.set_uniform(&r.program, "projection", &self.camera.projection()));
+
+        try!(bind_texture(d, &r.program, &r.texture));
+
+        try!(d.bind_vertex_array(&r.vao));
+        try!(d.draw_elements(r.indice_num));
+        try!(d.unbind_vertex_array());
         Ok(())
     }
 }
 
-fn calculate_normal(
+pub fn calculate_normal(
     v0: &cgmath::Vector3<f32>,
     v1: &cgmath::Vector3<f32>,
     v2: &cgmath::Vector3<f32>) -> cgmath::Vector3<f32> {
@@ -201,24 +355,30 @@ fn add_partial_vertices(
     let n0 = calculate_normal(&c, &lc, &ll);
     // println!("n: {}", n0);
     let diffuse = cgmath::Vector4::new(0.9,0.9,0.0,1.0);
-    vertices.push(Vertex::new(c, n0, diffuse));
-    vertices.push(Vertex::new(ll, n0, diffuse));
-    vertices.push(Vertex::new(lc, n0, diffuse));
+    vertices.push(Vertex::new(c, n0, diffuse, planar_uv(&c)));
+    vertices.push(Vertex::new(ll, n0, diffuse, planar_uv(&ll)));
+    vertices.push(Vertex::new(lc, n0, diffuse, planar_uv(&lc)));
 
     let n1 = calculate_normal(&c, &ss, &lc);
-    vertices.push(Vertex::new(c, n1, diffuse));
-    vertices.push(Vertex::new(lc, n1, diffuse));
-    vertices.push(Vertex::new(ss, n1, diffuse));
+    vertices.push(Vertex::new(c, n1, diffuse, planar_uv(&c)));
+    vertices.push(Vertex::new(lc, n1, diffuse, planar_uv(&lc)));
+    vertices.push(Vertex::new(ss, n1, diffuse, planar_uv(&ss)));
 
     let n2 = calculate_normal(&c, &rc, &ss);
-    vertices.push(Vertex::new(c, n2, diffuse));
-    vertices.push(Vertex::new(ss, n2, diffuse));
-    vertices.push(Vertex::new(rc, n2, diffuse));
+    vertices.push(Vertex::new(c, n2, diffuse, planar_uv(&c)));
+    vertices.push(Vertex::new(ss, n2, diffuse, planar_uv(&ss)));
+    vertices.push(Vertex::new(rc, n2, diffuse, planar_uv(&rc)));
 
     let n3 = calculate_normal(&c, &rl, &rc);
-    vertices.push(Vertex::new(c, n3, diffuse));
-    vertices.push(Vertex::new(rc, n3, diffuse));
-    vertices.push(Vertex::new(rl, n3, diffuse));
+    vertices.push(Vertex::new(c, n3, diffuse, planar_uv(&c)));
+    vertices.push(Vertex::new(rc, n3, diffuse, planar_uv(&rc)));
+    vertices.push(Vertex::new(rl, n3, diffuse, planar_uv(&rl)));
+}
+
+// Derive a simple planar texture coordinate for a star vertex by mapping its
+// x/y position (authored roughly in [-1,1]) into the [0,1] uv square.
+fn planar_uv(p: &cgmath::Vector3<f32>) -> cgmath::Vector2<f32> {
+    Vector2::new(p.x * 0.5 + 0.5, p.y * 0.5 + 0.5)
 }
 
 fn generate_vertices(geom: &Geometry) -> Vec<Vertex> {
@@ -234,7 +394,7 @@ fn generate_vertices(geom: &Geometry) -> Vec<Vertex> {
     // top right
     add_partial_vertices(c,
         lco,
-        rco, 
+        rco,
         cgmath::Vector3::new(0.0,ls,0.0),
         cgmath::Vector3::new(ls,0.0,0.0),
         cgmath::Vector3::new(ss,ss,0.0),
@@ -270,56 +430,211 @@ fn generate_vertices(geom: &Geometry) -> Vec<Vertex> {
     vertices
 }
 
-fn init_buffers(geom : &Geometry) -> Result<(GLuint, GLuint, i32), String> {
-    let vertices = generate_vertices(geom);
-    let mut vao = 0;
-    let mut vbo = 0;
-    let mut indice_num = 0;
+// Feed the global ambient color, the surface material and the active point
+// lights to the fragment shader. Point lights are uploaded into the
+// `point_lights[]` array along with the count the shader should iterate over.
+fn set_lighting_uniforms(d: &device::GlDevice,
+                         program: &Program,
+                         ambient: &Vector3<f32>,
+                         material: &light::Material,
+                         points: &[light::Point]) -> Result<(), String> {
+    try!(d.set_uniform(program, "ambient_color", ambient));
+    try!(d.set_uniform(program, "material.ambient", &material.ambient));
+    try!(d.set_uniform(program, "material.diffuse", &material.diffuse));
+    try!(d.set_uniform(program, "material.specular", &material.specular));
+    try!(d.set_uniform(program, "material.shininess", &material.shininess));
+
+    try!(d.set_uniform(program, "point_light_count", &(points.len() as i32)));
+    for (i, lp) in points.iter().enumerate() {
+        try!(d.set_uniform(program, format!("point_lights[{}].position", i).as_slice(), &lp.position));
+        try!(d.set_uniform(program, format!("point_lights[{}].constant", i).as_slice(), &lp.constant));
+        try!(d.set_uniform(program, format!("point_lights[{}].linear", i).as_slice(), &lp.linear));
+        try!(d.set_uniform(program, format!("point_lights[{}].quadratic", i).as_slice(), &lp.quadratic));
+    }
+    Ok(())
+}
+
+// Bind the object's texture to texture unit 0 and point the `texture0` sampler
+// at it. Objects without a texture render untextured and skip this entirely.
+fn bind_texture(d: &device::GlDevice, program: &Program, texture: &Option<Texture>) -> Result<(), String> {
+    match *texture {
+        Some(ref tex) => {
+            unsafe {
+                gl::ActiveTexture(gl::TEXTURE0);
+                try!(glutil::check_error());
+                gl::BindTexture(gl::TEXTURE_2D, tex.id());
+                try!(glutil::check_error());
+            }
+            try!(d.set_uniform(program, "texture0", &0i32));
+        },
+        None => {}
+    }
+    Ok(())
+}
+
+fn build_program(param: &Parameter) -> Result<gl::types::GLuint, String> {
+    let vss = match glutil::read_shader(param.vertex_shader_path) {
+        Ok(s) => s,
+        Err(e) => return Err(format!("Failed reading vertex shader: {}", e)),
+    };
+    let fss = match glutil::read_shader(param.fragment_shader_path) {
+        Ok(s) => s,
+        Err(e) => return Err(format!("Failed reading fragment shader: {}", e)),
+    };
+    let vs = try!(glutil::compile_shader(vss.as_slice(), gl::VERTEX_SHADER));
+    let fs = try!(glutil::compile_shader(fss.as_slice(), gl::FRAGMENT_SHADER));
+    let prog = try!(glutil::link_program(vs, fs));
+
+    // remove shaders since we've finished linking it
+    glutil::remove_shader(prog, vs);
+    glutil::remove_shader(prog, fs);
+
+    Ok(prog)
+}
+
+// Load an image from disk into a 2D GL texture. The handle comes from the
+// device as an RAII `Texture` returned to the caller; here we fill it in as RGBA, generate mipmaps and set it to
+// repeat with trilinear minification / linear magnification filtering.
+fn load_texture(d: &device::GlDevice, path: &str) -> Result<Texture, String> {
+    let img = match image::open(&Path::new(path)) {
+        Ok(i) => i,
+        Err(e) => return Err(format!("Failed loading texture {}: {}", path, e)),
+    };
+    let rgba = img.to_rgba();
+    let (width, height) = rgba.dimensions();
+    let data = rgba.into_vec();
+
+    let texture = try!(d.create_texture());
     unsafe {
-        // Create Vertex Array Object
-        gl::GenVertexArrays(1, &mut vao);
+        gl::BindTexture(gl::TEXTURE_2D, texture.id());
         try!(glutil::check_error());
-        gl::BindVertexArray(vao);
+        gl::TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_WRAP_S, gl::REPEAT as i32);
+        gl::TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_WRAP_T, gl::REPEAT as i32);
+        gl::TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_MIN_FILTER, gl::LINEAR_MIPMAP_LINEAR as i32);
+        gl::TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_MAG_FILTER, gl::LINEAR as i32);
         try!(glutil::check_error());
-        // Create a Vertex Buffer Object and copy the vertex data to it
-        gl::GenBuffers(1, &mut vbo);
+        gl::TexImage2D(gl::TEXTURE_2D, 0, gl::RGBA as i32,
+            width as i32, height as i32, 0,
+            gl::RGBA, gl::UNSIGNED_BYTE, mem::transmute(&data[0]));
         try!(glutil::check_error());
-        gl::BindBuffer(gl::ARRAY_BUFFER, vbo);
-        try!(glutil::check_error());
-        let vertice_size = mem::size_of::<Vertex>();
-        let vertice_num = vertices.len();
-        let float_size = mem::size_of::<GLfloat>();
-        // println!("Vertex size: {}", vertice_size);
-        // println!("Vertex num: {}", vertice_num);
-        // println!("float size: {}", float_size);
-        gl::BufferData(gl::ARRAY_BUFFER,
-            (vertice_num * vertice_size) as GLsizeiptr,
-            mem::transmute(&vertices[0]), gl::STATIC_DRAW);
+        gl::GenerateMipmap(gl::TEXTURE_2D);
         try!(glutil::check_error());
+        gl::BindTexture(gl::TEXTURE_2D, 0);
+    }
+    Ok(texture)
+}
 
-        indice_num = vertice_num as i32;
+// Quantize a coordinate so that vertices that are equal up to floating point
+// noise hash to the same bucket when deduplicating.
+fn quantize(f: f32) -> i32 {
+    (f * 100000.0) as i32
+}
 
-        // values taken from layout location in vertex shader
-        let pos_location = 0;
-        let norm_location = 1;
-        let diffuse_location = 2;
-        let stride = vertice_size as i32;
-        gl::EnableVertexAttribArray(pos_location);
-        try!(glutil::check_error());
-        gl::EnableVertexAttribArray(norm_location);
-        try!(glutil::check_error());
-        gl::EnableVertexAttribArray(diffuse_location);
-        try!(glutil::check_error());
-        gl::VertexAttribPointer(pos_location, 3, gl::FLOAT, gl::FALSE as GLboolean, stride, ptr::null());
-        try!(glutil::check_error());
-        let normal_offset = mem::transmute(float_size * 3);  // normal comes after position
-        gl::VertexAttribPointer(norm_location, 3, gl::FLOAT, gl::FALSE as GLboolean, stride, normal_offset);
-        try!(glutil::check_error());
-        let diffuse_offset = mem::transmute(float_size * (3+3)); // diffuse comes after position and normal
-        gl::VertexAttribPointer(diffuse_location, 4, gl::FLOAT, gl::FALSE as GLboolean, stride, diffuse_offset);
-        try!(glutil::check_error());
-        gl::BindVertexArray(0);
+// Key a vertex by its quantized (position, normal, diffuse, uv) tuple. Two
+// corners that agree on all four are considered the same vertex and share an
+// index; including uv keeps texture seams from collapsing into one vertex.
+fn vertex_key(v: &Vertex) -> (i32,i32,i32,i32,i32,i32,i32,i32,i32,i32,i32,i32) {
+    (quantize(v.position.x), quantize(v.position.y), quantize(v.position.z),
+     quantize(v.normal.x), quantize(v.normal.y), quantize(v.normal.z),
+     quantize(v.diffuse_color.x), quantize(v.diffuse_color.y),
+     quantize(v.diffuse_color.z), quantize(v.diffuse_color.w),
+     quantize(v.uv.x), quantize(v.uv.y))
+}
+
+// Collapse duplicated corners into a unique vertex list plus an index list,
+// so corners that agree on position, normal, diffuse and uv are stored only
+// once. Each star face carries its own flat normal, so the star's shared
+// center/canyon points still appear once per face.
+fn dedup_vertices(vertices: &Vec<Vertex>) -> (Vec<Vertex>, Vec<u32>) {
+    let mut unique: Vec<Vertex> = Vec::new();
+    let mut indices: Vec<u32> = Vec::new();
+    let mut map: HashMap<(i32,i32,i32,i32,i32,i32,i32,i32,i32,i32,i32,i32), u32> = HashMap::new();
+    for v in vertices.iter() {
+        let key = vertex_key(v);
+        let index = match map.find(&key) {
+            Some(&i) => i,
+            None => {
+                let i = unique.len() as u32;
+                unique.push(Vertex::new(v.position.clone(), v.normal.clone(), v.diffuse_color.clone(), v.uv.clone()));
+                map.insert(key, i);
+                i
+            }
+        };
+        indices.push(index);
     }
-    Ok((vao, vbo, indice_num))
+    (unique, indices)
 }
 
+// Build the full set of GL resources for an object from its deduplicated
+// geometry: a linked program, a vertex array capturing the vertex and element
+// buffers, the configured attributes and an optional texture. Everything comes
+// back as owned handles, so the caller just stores the `GlResource`.
+fn build_resource(d: &device::GlDevice, param: &Parameter, source: &Vec<Vertex>) -> Result<GlResource, String> {
+    let program = d.adopt_program(try!(build_program(param)));
+    let (vertices, indices) = dedup_vertices(source);
+
+    let vao = try!(d.create_vertex_array());
+    try!(d.bind_vertex_array(&vao));
+
+    // The vertex buffer binds to GL_ARRAY_BUFFER, which the attribute pointers
+    // below capture; the element buffer binds to GL_ELEMENT_ARRAY_BUFFER, which
+    // the vertex array records for us.
+    let vbo = try!(d.create_buffer());
+    try!(d.upload_buffer(&vbo, gl::ARRAY_BUFFER, vertices.as_slice(), gl::STATIC_DRAW));
+
+    let ebo = try!(d.create_buffer());
+    try!(d.upload_buffer(&ebo, gl::ELEMENT_ARRAY_BUFFER, indices.as_slice(), gl::STATIC_DRAW));
+
+    let stride = mem::size_of::<Vertex>() as i32;
+    try!(d.configure_attributes(Vertex::attributes().as_slice(), stride));
+    try!(d.unbind_vertex_array());
+
+    let texture = match param.texture_path {
+        Some(path) => Some(try!(load_texture(d, path))),
+        None => None,
+    };
+
+    Ok(GlResource {
+        program : program,
+        vao : vao,
+        vbo : vbo,
+        ebo : ebo,
+        texture : texture,
+        indice_num : indices.len() as i32,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use cgmath::{Vector2, Vector3, Vector4};
+    use super::{Vertex, dedup_vertices};
+
+    fn vertex(pos: Vector3<f32>, uv: Vector2<f32>) -> Vertex {
+        Vertex::new(pos,
+                    Vector3::new(0.0, 0.0, 1.0),
+                    Vector4::new(0.9, 0.9, 0.0, 1.0),
+                    uv)
+    }
+
+    #[test]
+    fn identical_corners_collapse_to_one() {
+        let p = Vector3::new(1.0, 2.0, 3.0);
+        let uv = Vector2::new(0.25, 0.75);
+        let source = vec![vertex(p, uv), vertex(p, uv)];
+        let (unique, indices) = dedup_vertices(&source);
+        assert_eq!(unique.len(), 1);
+        assert_eq!(indices, vec![0u32, 0u32]);
+    }
+
+    #[test]
+    fn seam_with_distinct_uv_is_preserved() {
+        // Same position/normal/diffuse but different uv: a texture seam that
+        // must stay two vertices so its UVs survive.
+        let p = Vector3::new(1.0, 2.0, 3.0);
+        let source = vec![vertex(p, Vector2::new(0.0, 0.0)),
+                          vertex(p, Vector2::new(1.0, 0.0))];
+        let (unique, indices) = dedup_vertices(&source);
+        assert_eq!(unique.len(), 2);
+        assert_eq!(indices, vec![0u32, 1u32]);
+    }
+}
@@ -0,0 +1,247 @@
+extern crate gl;
+extern crate cgmath;
+
+use gl::types::{GLuint,GLenum,GLint,GLsizeiptr,GLboolean};
+use std::mem;
+use std::ptr;
+use cgmath::{Vector3,Matrix4};
+
+use glutil;
+
+/// A GL buffer object. The handle is released when the value is dropped, so
+/// callers never call `glDeleteBuffers` by hand.
+pub struct Buffer {
+    id: GLuint,
+}
+
+impl Buffer {
+    pub fn id(&self) -> GLuint { self.id }
+}
+
+impl Drop for Buffer {
+    fn drop(&mut self) {
+        unsafe { gl::DeleteBuffers(1, &self.id); }
+    }
+}
+
+/// A vertex array object capturing attribute and element-buffer bindings.
+pub struct VertexArray {
+    id: GLuint,
+}
+
+impl VertexArray {
+    pub fn id(&self) -> GLuint { self.id }
+}
+
+impl Drop for VertexArray {
+    fn drop(&mut self) {
+        unsafe { gl::DeleteVertexArrays(1, &self.id); }
+    }
+}
+
+/// A linked shader program.
+pub struct Program {
+    id: GLuint,
+}
+
+impl Program {
+    pub fn id(&self) -> GLuint { self.id }
+}
+
+impl Drop for Program {
+    fn drop(&mut self) {
+        glutil::remove_program(self.id);
+    }
+}
+
+/// A 2D texture object.
+pub struct Texture {
+    id: GLuint,
+}
+
+impl Texture {
+    pub fn id(&self) -> GLuint { self.id }
+}
+
+impl Drop for Texture {
+    fn drop(&mut self) {
+        unsafe { gl::DeleteTextures(1, &self.id); }
+    }
+}
+
+// A single vertex attribute as seen by the shader: which location it binds to,
+// how many components it has, their GL type, the byte offset into the vertex
+// struct and whether fixed-point data should be normalized.
+pub struct VertexAttribute {
+    pub location: GLuint,
+    pub component_count: i32,
+    pub gl_type: GLenum,
+    pub offset: uint,
+    pub normalized: GLboolean,
+}
+
+/// A value that knows how to install itself into a uniform location. This is
+/// how `Device::set_uniform` is "overloaded" for the vector and matrix types
+/// the renderer uploads.
+pub trait UniformValue {
+    unsafe fn set(&self, location: GLint);
+}
+
+impl UniformValue for Vector3<f32> {
+    unsafe fn set(&self, location: GLint) {
+        gl::Uniform3f(location, self.x, self.y, self.z);
+    }
+}
+
+impl UniformValue for Matrix4<f32> {
+    // cgmath stores matrices column-major, matching OpenGL, so no transpose.
+    unsafe fn set(&self, location: GLint) {
+        gl::UniformMatrix4fv(location, 1, gl::FALSE as GLboolean, mem::transmute(self));
+    }
+}
+
+impl UniformValue for f32 {
+    unsafe fn set(&self, location: GLint) {
+        gl::Uniform1f(location, *self);
+    }
+}
+
+impl UniformValue for i32 {
+    unsafe fn set(&self, location: GLint) {
+        gl::Uniform1i(location, *self);
+    }
+}
+
+/// A thin wrapper over the GL calls the renderer needs. Every operation runs
+/// its own `glutil::check_error()` so callers no longer interleave raw
+/// `gl::*` calls with error checks, and every resource comes back as an
+/// RAII handle that frees itself on drop.
+pub trait Device {
+    fn create_buffer(&self) -> Result<Buffer, String>;
+    fn upload_buffer<T>(&self, buffer: &Buffer, target: GLenum, data: &[T], usage: GLenum) -> Result<(), String>;
+    fn create_vertex_array(&self) -> Result<VertexArray, String>;
+    fn bind_vertex_array(&self, vao: &VertexArray) -> Result<(), String>;
+    fn unbind_vertex_array(&self) -> Result<(), String>;
+    fn configure_attributes(&self, attributes: &[VertexAttribute], stride: i32) -> Result<(), String>;
+    fn create_texture(&self) -> Result<Texture, String>;
+    fn use_program(&self, program: &Program) -> Result<(), String>;
+    fn set_uniform<T: UniformValue>(&self, program: &Program, name: &str, value: &T) -> Result<(), String>;
+    fn draw_elements(&self, count: i32) -> Result<(), String>;
+}
+
+/// The default `Device` backed by live OpenGL calls.
+pub struct GlDevice;
+
+impl GlDevice {
+    pub fn new() -> GlDevice { GlDevice }
+
+    // Wrap a freshly generated program id as an owned handle. Shader
+    // compilation and linking still live in `glutil`; this only takes over the
+    // lifetime so the program is deleted on drop.
+    pub fn adopt_program(&self, id: GLuint) -> Program {
+        Program { id: id }
+    }
+}
+
+impl Device for GlDevice {
+    fn create_buffer(&self) -> Result<Buffer, String> {
+        let mut id = 0;
+        unsafe {
+            gl::GenBuffers(1, &mut id);
+            try!(glutil::check_error());
+        }
+        Ok(Buffer { id: id })
+    }
+
+    fn upload_buffer<T>(&self, buffer: &Buffer, target: GLenum, data: &[T], usage: GLenum) -> Result<(), String> {
+        let size = (data.len() * mem::size_of::<T>()) as GLsizeiptr;
+        // An empty slice must not be indexed; upload a zero-sized buffer with a
+        // null data pointer instead so a degenerate mesh can't panic here.
+        let ptr = if data.is_empty() {
+            ptr::null()
+        } else {
+            unsafe { mem::transmute(&data[0]) }
+        };
+        unsafe {
+            gl::BindBuffer(target, buffer.id);
+            try!(glutil::check_error());
+            gl::BufferData(target, size, ptr, usage);
+            try!(glutil::check_error());
+        }
+        Ok(())
+    }
+
+    fn create_vertex_array(&self) -> Result<VertexArray, String> {
+        let mut id = 0;
+        unsafe {
+            gl::GenVertexArrays(1, &mut id);
+            try!(glutil::check_error());
+        }
+        Ok(VertexArray { id: id })
+    }
+
+    fn bind_vertex_array(&self, vao: &VertexArray) -> Result<(), String> {
+        unsafe {
+            gl::BindVertexArray(vao.id);
+            try!(glutil::check_error());
+        }
+        Ok(())
+    }
+
+    fn unbind_vertex_array(&self) -> Result<(), String> {
+        unsafe {
+            gl::BindVertexArray(0);
+            try!(glutil::check_error());
+        }
+        Ok(())
+    }
+
+    fn configure_attributes(&self, attributes: &[VertexAttribute], stride: i32) -> Result<(), String> {
+        unsafe {
+            for attr in attributes.iter() {
+                gl::EnableVertexAttribArray(attr.location);
+                try!(glutil::check_error());
+                gl::VertexAttribPointer(attr.location, attr.component_count, attr.gl_type,
+                    attr.normalized, stride, mem::transmute(attr.offset));
+                try!(glutil::check_error());
+            }
+        }
+        Ok(())
+    }
+
+    fn create_texture(&self) -> Result<Texture, String> {
+        let mut id = 0;
+        unsafe {
+            gl::GenTextures(1, &mut id);
+            try!(glutil::check_error());
+        }
+        Ok(Texture { id: id })
+    }
+
+    fn use_program(&self, program: &Program) -> Result<(), String> {
+        unsafe {
+            gl::UseProgram(program.id);
+            try!(glutil::check_error());
+        }
+        Ok(())
+    }
+
+    fn set_uniform<T: UniformValue>(&self, program: &Program, name: &str, value: &T) -> Result<(), String> {
+        unsafe {
+            let cstr = name.to_c_str();
+            let location = gl::GetUniformLocation(program.id, cstr.as_ptr());
+            try!(glutil::check_error());
+            value.set(location);
+            try!(glutil::check_error());
+        }
+        Ok(())
+    }
+
+    fn draw_elements(&self, count: i32) -> Result<(), String> {
+        unsafe {
+            gl::DrawElements(gl::TRIANGLES, count, gl::UNSIGNED_INT, ptr::null());
+            try!(glutil::check_error());
+        }
+        Ok(())
+    }
+}
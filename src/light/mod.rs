@@ -0,0 +1,60 @@
+extern crate cgmath;
+
+use cgmath::Vector3;
+
+/// A light that shines from a fixed direction, as if infinitely far away.
+/// `position` is taken relative to the lit object's center to derive the
+/// direction-to-light that the fragment shader consumes.
+pub struct Directional {
+    pub name: String,
+    pub position: Vector3<f32>,
+}
+
+impl Directional {
+    pub fn new(name: String, position: Vector3<f32>) -> Directional {
+        Directional {
+            name : name,
+            position : position,
+        }
+    }
+}
+
+/// A light located at a point in space. Its contribution falls off with
+/// distance through the usual constant/linear/quadratic attenuation terms.
+pub struct Point {
+    pub position: Vector3<f32>,
+    pub constant: f32,
+    pub linear: f32,
+    pub quadratic: f32,
+}
+
+impl Point {
+    pub fn new(position: Vector3<f32>, constant: f32, linear: f32, quadratic: f32) -> Point {
+        Point {
+            position : position,
+            constant : constant,
+            linear : linear,
+            quadratic : quadratic,
+        }
+    }
+}
+
+/// The surface response of a material under Phong shading: separate ambient,
+/// diffuse and specular colors plus the specular `shininess` exponent.
+pub struct Material {
+    pub ambient: Vector3<f32>,
+    pub diffuse: Vector3<f32>,
+    pub specular: Vector3<f32>,
+    pub shininess: f32,
+}
+
+impl Material {
+    pub fn new(ambient: Vector3<f32>, diffuse: Vector3<f32>, specular: Vector3<f32>, shininess: f32) -> Material {
+        Material {
+            ambient : ambient,
+            diffuse : diffuse,
+            specular : specular,
+            shininess : shininess,
+        }
+    }
+}
@@ -0,0 +1,232 @@
+extern crate cgmath;
+
+use std::io::{BufferedReader, File};
+use cgmath::{Vector2, Vector3, Vector4};
+
+use christmas_star::{Vertex, calculate_normal};
+
+// OBJ files carry no per-vertex color, so loaded meshes reuse the same
+// default diffuse the procedural star is authored with.
+static DEFAULT_DIFFUSE: Vector4<f32> = Vector4 { x: 0.9, y: 0.9, z: 0.0, w: 1.0 };
+
+// Faces without a `vt` reference fall back to the origin of uv space.
+static DEFAULT_UV: Vector2<f32> = Vector2 { x: 0.0, y: 0.0 };
+
+// A single corner of a face: 1-based indices into the position, texcoord and
+// normal arrays. Texcoord and normal are optional, matching the `pos/uv/normal`
+// syntax where the trailing components may be left out.
+struct Point {
+    position: uint,
+    texcoord: Option<uint>,
+    normal: Option<uint>,
+}
+
+/// Parse a Wavefront OBJ file and expand its faces into the existing `Vertex`
+/// layout. Polygons with more than three corners are fan-triangulated. Faces
+/// that do not supply a normal fall back to a flat normal computed over the
+/// triangle with `calculate_normal`.
+pub fn load_obj(path: &str) -> Result<Vec<Vertex>, String> {
+    let file = match File::open(&Path::new(path)) {
+        Ok(f) => f,
+        Err(e) => return Err(format!("Failed opening {}: {}", path, e)),
+    };
+    let mut reader = BufferedReader::new(file);
+
+    let mut positions: Vec<Vector3<f32>> = Vec::new();
+    let mut texcoords: Vec<Vector2<f32>> = Vec::new();
+    let mut normals: Vec<Vector3<f32>> = Vec::new();
+    let mut faces: Vec<Vec<Point>> = Vec::new();
+
+    for line in reader.lines() {
+        let line = match line {
+            Ok(l) => l,
+            Err(e) => return Err(format!("Failed reading {}: {}", path, e)),
+        };
+        let line = line.as_slice().trim();
+        if line.is_empty() || line.starts_with("#") {
+            continue;
+        }
+        let tokens: Vec<&str> = line.words().collect();
+        match tokens[0] {
+            "v"  => positions.push(try!(parse_vector3(tokens.tail()))),
+            "vt" => texcoords.push(try!(parse_vector2(tokens.tail()))),
+            "vn" => normals.push(try!(parse_vector3(tokens.tail()))),
+            "f"  => faces.push(try!(parse_face(tokens.tail(),
+                                               positions.len(),
+                                               texcoords.len(),
+                                               normals.len()))),
+            // o / g / s / usemtl / mtllib and anything else are ignored.
+            _ => {}
+        }
+    }
+
+    Ok(triangulate(&positions, &texcoords, &normals, &faces))
+}
+
+// Parse the first three whitespace-separated floats. The optional `w`
+// component of `v` lines is accepted but discarded.
+fn parse_vector3(tokens: &[&str]) -> Result<Vector3<f32>, String> {
+    if tokens.len() < 3 {
+        return Err(format!("Expected 3 floats, got {}", tokens.len()));
+    }
+    Ok(Vector3::new(try!(parse_float(tokens[0])),
+                    try!(parse_float(tokens[1])),
+                    try!(parse_float(tokens[2]))))
+}
+
+fn parse_vector2(tokens: &[&str]) -> Result<Vector2<f32>, String> {
+    if tokens.len() < 2 {
+        return Err(format!("Expected 2 floats, got {}", tokens.len()));
+    }
+    Ok(Vector2::new(try!(parse_float(tokens[0])),
+                    try!(parse_float(tokens[1]))))
+}
+
+fn parse_float(s: &str) -> Result<f32, String> {
+    match from_str::<f32>(s) {
+        Some(f) => Ok(f),
+        None => Err(format!("Failed parsing float '{}'", s)),
+    }
+}
+
+// Parse a single `f` statement into its corner points. `pos_len`/`tex_len`/
+// `norm_len` are the counts seen so far, used to resolve negative indices
+// (which count back from the most recently defined element).
+fn parse_face(tokens: &[&str], pos_len: uint, tex_len: uint, norm_len: uint)
+    -> Result<Vec<Point>, String> {
+    let mut points: Vec<Point> = Vec::new();
+    for token in tokens.iter() {
+        let parts: Vec<&str> = token.split('/').collect();
+        let position = try!(resolve_index(parts[0], pos_len));
+        let texcoord = if parts.len() > 1 && !parts[1].is_empty() {
+            Some(try!(resolve_index(parts[1], tex_len)))
+        } else {
+            None
+        };
+        let normal = if parts.len() > 2 && !parts[2].is_empty() {
+            Some(try!(resolve_index(parts[2], norm_len)))
+        } else {
+            None
+        };
+        points.push(Point { position: position, texcoord: texcoord, normal: normal });
+    }
+    if points.len() < 3 {
+        return Err(format!("Face has fewer than 3 vertices: {}", points.len()));
+    }
+    Ok(points)
+}
+
+// Turn a 1-based (or negative) OBJ index into a 0-based array index.
+fn resolve_index(s: &str, len: uint) -> Result<uint, String> {
+    match from_str::<int>(s) {
+        Some(i) if i > 0 => {
+            let index = (i - 1) as uint;
+            if index >= len {
+                Err(format!("Index {} out of range (len {})", i, len))
+            } else {
+                Ok(index)
+            }
+        },
+        Some(i) if i < 0 => {
+            let offset = (-i) as uint;
+            if offset > len {
+                Err(format!("Negative index {} out of range (len {})", i, len))
+            } else {
+                Ok(len - offset)
+            }
+        },
+        _ => Err(format!("Invalid face index '{}'", s)),
+    }
+}
+
+// Fan-triangulate every face and resolve its corners into vertices. For a face
+// with corners v0..vn this emits the triangles (v0, vi, vi+1).
+fn triangulate(positions: &Vec<Vector3<f32>>,
+               texcoords: &Vec<Vector2<f32>>,
+               normals: &Vec<Vector3<f32>>,
+               faces: &Vec<Vec<Point>>) -> Vec<Vertex> {
+    let mut vertices: Vec<Vertex> = Vec::new();
+    for face in faces.iter() {
+        for i in range(1, face.len() - 1) {
+            let tri = [&face[0], &face[i], &face[i + 1]];
+            let p0 = positions[tri[0].position];
+            let p1 = positions[tri[1].position];
+            let p2 = positions[tri[2].position];
+            let flat = calculate_normal(&p0, &p1, &p2);
+            for point in tri.iter() {
+                let pos = positions[point.position];
+                let norm = match point.normal {
+                    Some(n) => normals[n],
+                    None => flat,
+                };
+                let uv = match point.texcoord {
+                    Some(t) => texcoords[t],
+                    None => DEFAULT_UV,
+                };
+                vertices.push(Vertex::new(pos, norm, DEFAULT_DIFFUSE, uv));
+            }
+        }
+    }
+    vertices
+}
+
+#[cfg(test)]
+mod tests {
+    use cgmath::{Vector2, Vector3};
+    use super::{resolve_index, parse_face, triangulate};
+
+    #[test]
+    fn resolve_positive_index() {
+        assert_eq!(resolve_index("1", 3), Ok(0u));
+        assert_eq!(resolve_index("3", 3), Ok(2u));
+    }
+
+    #[test]
+    fn resolve_negative_index() {
+        assert_eq!(resolve_index("-1", 3), Ok(2u));
+        assert_eq!(resolve_index("-3", 3), Ok(0u));
+    }
+
+    #[test]
+    fn resolve_zero_is_error() {
+        assert!(resolve_index("0", 3).is_err());
+    }
+
+    #[test]
+    fn resolve_out_of_range_is_error() {
+        assert!(resolve_index("4", 3).is_err());
+        assert!(resolve_index("-4", 3).is_err());
+    }
+
+    #[test]
+    fn parse_face_texcoord_only() {
+        // `v/t` corners carry a texcoord but no normal.
+        let face = parse_face(&["1/1", "2/2", "3/3"], 3, 3, 0).unwrap();
+        assert_eq!(face.len(), 3);
+        assert_eq!(face[0].position, 0);
+        assert_eq!(face[0].texcoord, Some(0u));
+        assert_eq!(face[0].normal, None);
+    }
+
+    #[test]
+    fn parse_face_normal_only() {
+        // `v//n` corners skip the texcoord but keep the normal.
+        let face = parse_face(&["1//1", "2//2", "3//3"], 3, 0, 3).unwrap();
+        assert_eq!(face[0].texcoord, None);
+        assert_eq!(face[0].normal, Some(0u));
+    }
+
+    #[test]
+    fn triangulate_quad_fans_into_two_triangles() {
+        let positions = vec![Vector3::new(0.0, 0.0, 0.0),
+                             Vector3::new(1.0, 0.0, 0.0),
+                             Vector3::new(1.0, 1.0, 0.0),
+                             Vector3::new(0.0, 1.0, 0.0)];
+        let texcoords: Vec<Vector2<f32>> = Vec::new();
+        let normals: Vec<Vector3<f32>> = Vec::new();
+        let faces = vec![parse_face(&["1", "2", "3", "4"], 4, 0, 0).unwrap()];
+        let vertices = triangulate(&positions, &texcoords, &normals, &faces);
+        // A quad fans into (v0,v1,v2) and (v0,v2,v3): two triangles, six corners.
+        assert_eq!(vertices.len(), 6);
+    }
+}